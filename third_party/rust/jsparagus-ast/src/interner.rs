@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// Typed index into an `Interner<T>`.
+///
+/// This is a newtype over `usize` parameterized by the interned type, so
+/// an `Idx<Foo>` can't be used to index an `Interner<Bar>` by accident.
+pub struct Idx<T> {
+    index: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Idx<T> {
+    pub(crate) fn new(index: usize) -> Self {
+        Self {
+            index,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn into_raw(self) -> usize {
+        self.index
+    }
+}
+
+// Manual impls: `#[derive(...)]` would otherwise require `T: Clone` /
+// `T: PartialEq` / `T: Debug` / etc, even though `Idx<T>` only ever stores
+// a `usize` and never actually holds a `T`.
+impl<T> Clone for Idx<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Idx<T> {}
+impl<T> PartialEq for Idx<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+impl<T> Eq for Idx<T> {}
+impl<T> Hash for Idx<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+impl<T> fmt::Debug for Idx<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Idx").field("index", &self.index).finish()
+    }
+}
+
+/// A generic interner: owns a `Vec<T>` plus a dedup map, and hands out a
+/// stable, typed `Idx<T>` for each distinct value. This is the shared
+/// machinery behind `SourceAtomSet` and any other subsystem that needs to
+/// deduplicate repeated values into stable indices.
+///
+/// `indices` uses `std`'s randomized `HashMap`, not a hand-rolled one, on
+/// purpose: an interner sees attacker-controlled input (e.g. every
+/// identifier in a parsed script), and a fixed, public hash function would
+/// let that input be crafted to collide and degrade `intern` to O(n).
+#[derive(Debug)]
+pub struct Interner<T: Eq + Hash + Clone> {
+    values: Vec<T>,
+    indices: HashMap<T, Idx<T>>,
+}
+
+impl<T: Eq + Hash + Clone> Default for Interner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Eq + Hash + Clone> Interner<T> {
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Intern `value`, returning its existing `Idx<T>` if an equal value
+    /// was interned before, or a freshly allocated one otherwise.
+    pub fn intern(&mut self, value: T) -> Idx<T> {
+        if let Some(&index) = self.indices.get(&value) {
+            return index;
+        }
+
+        let index = Idx::new(self.values.len());
+        self.values.push(value.clone());
+        self.indices.insert(value, index);
+        index
+    }
+
+    /// Borrow the value at `index`, without allocating.
+    pub fn lookup(&self, index: Idx<T>) -> &T {
+        &self.values[index.into_raw()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Idx<T>, &T)> {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (Idx::new(index), value))
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.values
+    }
+}
@@ -1,19 +1,9 @@
-use std::collections::HashMap;
+use std::rc::Rc;
 
-/// Index into SourceAtomSet.atoms.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub struct SourceAtomSetIndex {
-    index: usize,
-}
-impl SourceAtomSetIndex {
-    fn new(index: usize) -> Self {
-        Self { index }
-    }
+use crate::interner::{Idx, Interner};
 
-    pub fn into_raw(self) -> usize {
-        self.index
-    }
-}
+/// Index into SourceAtomSet.
+pub type SourceAtomSetIndex = Idx<Atom>;
 
 // Call $handler macro with the list of common atoms.
 //
@@ -150,41 +140,180 @@ macro_rules! define_struct {
 }
 for_all_common_atoms!(define_struct);
 
+// Define COMMON_ATOM_KEYS, the list of common atom strings in the same
+// order as the `CommonAtoms` discriminants, so that an index into this
+// slice is directly usable as a `SourceAtomSetIndex`.
+macro_rules! define_keys {
+    ($(($s:tt, $method:ident, $variant:ident),)*) => {
+        const COMMON_ATOM_KEYS: &[&str] = &[$($s,)*];
+    };
+}
+for_all_common_atoms!(define_keys);
+
+// Minimal perfect hash over `COMMON_ATOM_KEYS`, so that `insert` can
+// recognize a keyword in O(1) without touching the dynamic interner. This
+// uses a two-level CHD scheme: `h1` picks a bucket, each bucket has a
+// displacement `disp[bucket]` baked in below, and `h2 + disp` gives the
+// final slot in a table of size `PHF_TABLE_SIZE`. The displacement and
+// slot tables were generated offline for the fixed keyword list above; if
+// `for_all_common_atoms!` ever changes, they need to be regenerated (e.g.
+// with a small script that brute-forces displacements per bucket).
+const PHF_NUM_BUCKETS: u64 = 51;
+const PHF_TABLE_SIZE: u64 = 51;
+
+const PHF_DISPLACEMENTS: [u8; 51] = [
+    2, 4, 1, 2, 0, 0, 4, 0, 0, 1, 3, 0, 0, 0, 5, 0, 0, 4, 0, 0, 0, 0, 0, 12, 8, 0, 10, 0, 0, 2, 6,
+    0, 0, 0, 0, 0, 0, 3, 0, 0, 1, 0, 0, 24, 0, 11, 48, 0, 0, 0, 1,
+];
+
+// Index into `COMMON_ATOM_KEYS` for each slot, or -1 if the slot is empty.
+// The table is minimal (one slot per key), so every slot is filled.
+const PHF_SLOTS: [i8; 51] = [
+    24, 7, 39, 12, 19, 1, 46, 11, 42, 31, 38, 44, 41, 18, 33, 25, 16, 35, 23, 20, 6, 17, 27, 34, 2,
+    50, 14, 10, 26, 49, 45, 29, 32, 8, 36, 22, 4, 9, 30, 13, 0, 48, 3, 28, 40, 43, 15, 21, 47, 37, 5,
+];
+
+// FNV-1a, seeded so that the bucket hash (`seed` 0) and the slot hash
+// (`seed` 1) are independent. `const fn` so the table below can be
+// verified at compile time.
+const fn phf_hash(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325 ^ seed.wrapping_mul(0x0100_0193);
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+// Resolve `bytes` to a slot in `PHF_SLOTS`, shared by `lookup_common_atom`
+// and the compile-time collision check below.
+const fn phf_slot(bytes: &[u8]) -> usize {
+    let bucket = (phf_hash(bytes, 0) % PHF_NUM_BUCKETS) as usize;
+    let disp = PHF_DISPLACEMENTS[bucket] as u64;
+    ((phf_hash(bytes, 1).wrapping_add(disp)) % PHF_TABLE_SIZE) as usize
+}
+
+// Look up `s` in the common-atom perfect hash table, returning its
+// `SourceAtomSetIndex` if `s` is one of the keywords in
+// `for_all_common_atoms!`.
+fn lookup_common_atom(s: &str) -> Option<SourceAtomSetIndex> {
+    let key_index = PHF_SLOTS[phf_slot(s.as_bytes())];
+    if key_index < 0 {
+        return None;
+    }
+    let key_index = key_index as usize;
+    if COMMON_ATOM_KEYS[key_index] != s {
+        // Hash collision with a non-keyword string.
+        return None;
+    }
+    Some(SourceAtomSetIndex::new(key_index))
+}
+
+// Verify, at compile time, that every key in `COMMON_ATOM_KEYS` resolves
+// through `PHF_DISPLACEMENTS`/`PHF_SLOTS` back to its own index, i.e. that
+// the table has no collisions for the current keyword list. If
+// `for_all_common_atoms!` changes and the table isn't regenerated to
+// match, this fails the build instead of silently losing the fast path.
+const fn common_atom_phf_is_collision_free() -> bool {
+    let mut i = 0;
+    while i < COMMON_ATOM_KEYS.len() {
+        let slot = phf_slot(COMMON_ATOM_KEYS[i].as_bytes());
+        if PHF_SLOTS[slot] < 0 || PHF_SLOTS[slot] as usize != i {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = assert!(
+    common_atom_phf_is_collision_free(),
+    "PHF_DISPLACEMENTS/PHF_SLOTS is stale for COMMON_ATOM_KEYS; regenerate it \
+     (see the comment above PHF_NUM_BUCKETS)"
+);
+
+// Inline capacity of `Atom`, in bytes. Chosen so that `Atom` stays a
+// reasonably small value type while covering the large majority of
+// identifiers seen in real-world scripts (most are well under 15 bytes).
+const ATOM_INLINE_CAPACITY: usize = 15;
+
+/// A single interned string, stored inline when short enough to avoid a
+/// heap allocation. Longer strings spill to a reference-counted `str`, so
+/// cloning a heap-backed `Atom` (e.g. into `Interner`'s dedup map) is a
+/// refcount bump rather than a reallocation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Atom {
+    Inline {
+        len: u8,
+        buf: [u8; ATOM_INLINE_CAPACITY],
+    },
+    Heap(Rc<str>),
+}
+
+impl Atom {
+    fn new(s: &str) -> Self {
+        if s.len() <= ATOM_INLINE_CAPACITY {
+            let mut buf = [0u8; ATOM_INLINE_CAPACITY];
+            buf[..s.len()].copy_from_slice(s.as_bytes());
+            Atom::Inline {
+                len: s.len() as u8,
+                buf,
+            }
+        } else {
+            Atom::Heap(s.into())
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Atom::Inline { len, buf } => std::str::from_utf8(&buf[..*len as usize])
+                .expect("Atom::Inline always holds bytes copied from a valid &str"),
+            Atom::Heap(s) => s,
+        }
+    }
+}
+
 /// Set of atoms, including the following:
 ///
 ///   * atoms referred from bytecode
 ///   * variable names referred from scope data
 ///
 /// WARNING: This set itself does *NOT* map to JSScript::atoms().
+///
+/// This is a thin, string-flavored wrapper around the generic `Interner`,
+/// layering the fixed common-atom perfect hash on top of it.
 #[derive(Debug)]
-pub struct SourceAtomSet<'alloc> {
-    atoms: Vec<String>,
+pub struct SourceAtomSet {
+    interner: Interner<Atom>,
+}
 
-    /// Cache for the case the same string is inserted multiple times.
-    atom_indices: HashMap<&'alloc str, SourceAtomSetIndex>,
+impl Default for SourceAtomSet {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<'alloc> SourceAtomSet<'alloc> {
+impl SourceAtomSet {
     // Create a set, with all common atoms inserted.
     pub fn new() -> Self {
         let mut result = Self {
-            atoms: Vec::new(),
-            atom_indices: HashMap::new(),
+            interner: Interner::new(),
         };
         result.insert_common_atoms();
         result
     }
 
-    // Insert all common atoms.
+    // Insert all common atoms. These are looked up through
+    // `lookup_common_atom` rather than the interner's dedup map, but still
+    // need to occupy their fixed slots at the front of the interner.
     fn insert_common_atoms(&mut self) {
         macro_rules! insert_atom {
             ($self: ident,
              $(($s:tt, $method:ident, $variant:ident),)*) => {
                 $(
-                    $self.atoms.push($s.to_string());
-                    $self
-                        .atom_indices
-                        .insert($s, CommonSourceAtomSetIndices::$method());
+                    $self.interner.intern(Atom::new($s));
                 )*
             };
         }
@@ -197,29 +326,127 @@ impl<'alloc> SourceAtomSet<'alloc> {
     // it with the result of this method.
     pub fn new_uninitialized() -> Self {
         Self {
-            atoms: Vec::new(),
-            atom_indices: HashMap::new(),
+            interner: Interner::new(),
         }
     }
 
-    pub fn insert(&mut self, s: &'alloc str) -> SourceAtomSetIndex {
-        match self.atom_indices.get(s) {
-            Some(index) => return *index,
-            _ => {}
+    pub fn insert(&mut self, s: &str) -> SourceAtomSetIndex {
+        if let Some(index) = lookup_common_atom(s) {
+            return index;
         }
 
-        let index = self.atoms.len();
-        self.atoms.push(s.to_string());
-        let result = SourceAtomSetIndex::new(index);
-        self.atom_indices.insert(s, result);
-        result
+        self.interner.intern(Atom::new(s))
     }
 
     pub fn get(&self, index: SourceAtomSetIndex) -> String {
-        self.atoms[index.into_raw()].clone()
+        self.get_str(index).to_string()
+    }
+
+    /// Borrow the interned string at `index`, without allocating.
+    pub fn get_str(&self, index: SourceAtomSetIndex) -> &str {
+        self.interner.lookup(index).as_str()
+    }
+
+    /// Number of atoms in the set, including the common atoms.
+    pub fn len(&self) -> usize {
+        self.interner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interner.is_empty()
+    }
+
+    /// Iterate over all interned atoms in index order, without allocating.
+    pub fn iter(&self) -> impl Iterator<Item = (SourceAtomSetIndex, &str)> {
+        self.interner
+            .iter()
+            .map(|(index, atom)| (index, atom.as_str()))
     }
 
     pub fn into_vec(self) -> Vec<String> {
-        self.atoms
+        self.interner
+            .into_vec()
+            .into_iter()
+            .map(|atom| atom.as_str().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_atoms_match_common_source_atom_set_indices() {
+        let mut set = SourceAtomSet::new();
+        assert_eq!(
+            set.insert("arguments"),
+            CommonSourceAtomSetIndices::arguments()
+        );
+        assert_eq!(set.insert("yield"), CommonSourceAtomSetIndices::yield_());
+        assert_eq!(
+            set.insert("use strict"),
+            CommonSourceAtomSetIndices::use_strict()
+        );
+        assert_eq!(
+            set.insert("__proto__"),
+            CommonSourceAtomSetIndices::__proto__()
+        );
+    }
+
+    #[test]
+    fn insert_dedups_inline_atoms() {
+        let mut set = SourceAtomSet::new();
+        let len_before = set.len();
+
+        let first = set.insert("short");
+        let second = set.insert("short");
+        assert_eq!(first, second);
+        assert_eq!(set.len(), len_before + 1);
+    }
+
+    #[test]
+    fn insert_dedups_heap_atoms() {
+        let mut set = SourceAtomSet::new();
+        let len_before = set.len();
+
+        let long = "this identifier is definitely longer than fifteen bytes";
+        let first = set.insert(long);
+        let second = set.insert(long);
+        assert_eq!(first, second);
+        assert_eq!(set.len(), len_before + 1);
+    }
+
+    #[test]
+    fn inline_heap_boundary() {
+        let fifteen = "a".repeat(ATOM_INLINE_CAPACITY);
+        let sixteen = "a".repeat(ATOM_INLINE_CAPACITY + 1);
+
+        assert!(matches!(Atom::new(&fifteen), Atom::Inline { .. }));
+        assert!(matches!(Atom::new(&sixteen), Atom::Heap(_)));
+    }
+
+    #[test]
+    fn iter_into_vec_and_len_agree() {
+        let mut set = SourceAtomSet::new();
+        set.insert("short");
+        set.insert("this identifier is definitely longer than fifteen bytes");
+
+        let len = set.len();
+        let iterated: Vec<(SourceAtomSetIndex, String)> = set
+            .iter()
+            .map(|(index, s)| (index, s.to_string()))
+            .collect();
+        assert_eq!(iterated.len(), len);
+
+        let indices: Vec<SourceAtomSetIndex> = iterated.iter().map(|(index, _)| *index).collect();
+        let strings: Vec<String> = iterated.into_iter().map(|(_, s)| s).collect();
+
+        let vec = set.into_vec();
+        assert_eq!(vec.len(), len);
+        assert_eq!(vec, strings);
+        for (i, index) in indices.into_iter().enumerate() {
+            assert_eq!(index.into_raw(), i);
+        }
     }
 }